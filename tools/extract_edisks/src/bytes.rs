@@ -0,0 +1,18 @@
+//
+// Big-endian integer reads.
+//
+// Every Mac OS on-disk structure this crate parses (edisk headers, HFS/MFS
+// volumes) is big-endian 68k-era data, so these small helpers are shared
+// across those parsers instead of each re-deriving them.
+//
+
+pub fn read_long(mem: &[u8], addr: usize) -> u32 {
+    ((mem[addr] as u32) << 24)
+        | ((mem[addr + 1] as u32) << 16)
+        | ((mem[addr + 2] as u32) << 8)
+        | (mem[addr + 3] as u32)
+}
+
+pub fn read_word(mem: &[u8], addr: usize) -> u16 {
+    ((mem[addr] as u16) << 8) | (mem[addr + 1] as u16)
+}
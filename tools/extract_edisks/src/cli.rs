@@ -0,0 +1,341 @@
+//
+// Command line interface.
+//
+// The scanning loop used to be hard-coded in `main` against a fixed ROM
+// path, always writing every edisk it found. This wires the same scan up
+// to `argp` subcommands so the ROM path, output directory, and which
+// edisk(s) to look at are all caller-controlled.
+//
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use argp::FromArgs;
+
+use crate::codec::CodecRegistry;
+use crate::edisk::{self, EdiskHeader, EDISK_SCAN_STRIDE};
+use crate::encoder;
+use crate::hfs::Volume;
+use crate::rom::RomReader;
+use crate::verify::{HashDatabase, VerifyResult};
+
+#[derive(FromArgs)]
+/// Extract and inspect edisks embedded in a Mac Classic ROM image.
+pub struct Args {
+    /// path to the ROM image
+    #[argp(positional)]
+    rom: PathBuf,
+
+    /// directory to write extracted disks to (default: current directory)
+    #[argp(option, default = "PathBuf::from(\".\")")]
+    out_dir: PathBuf,
+
+    /// only look at the edisk at this byte offset into the ROM
+    #[argp(option)]
+    offset: Option<u64>,
+
+    /// compare extracted disks' hashes against a datfile mapping SHA-1 to
+    /// known disk name, reporting a match/mismatch per edisk
+    #[argp(option)]
+    verify: Option<PathBuf>,
+
+    #[argp(subcommand)]
+    command: Command,
+}
+
+#[derive(FromArgs)]
+#[argp(subcommand)]
+enum Command {
+    List(ListCommand),
+    Info(InfoCommand),
+    Extract(ExtractCommand),
+    Ls(LsCommand),
+    Cat(CatCommand),
+    Repack(RepackCommand),
+}
+
+/// Print each discovered edisk's location, version, block size, and disk
+/// length, without writing anything.
+#[derive(FromArgs)]
+#[argp(subcommand, name = "list")]
+struct ListCommand {}
+
+/// Dump an edisk's parsed header fields (version, block size, disk length,
+/// table and data offsets), a histogram of block modes, and the decoded
+/// disk's hashes. Other bytes of the 512-byte header are not shown.
+#[derive(FromArgs)]
+#[argp(subcommand, name = "info")]
+struct InfoCommand {}
+
+/// Decode edisks to `.dsk` files (the default behavior of earlier versions
+/// of this tool).
+#[derive(FromArgs)]
+#[argp(subcommand, name = "extract")]
+struct ExtractCommand {}
+
+/// List the files on an edisk's HFS/MFS volume, without writing a `.dsk`.
+#[derive(FromArgs)]
+#[argp(subcommand, name = "ls")]
+struct LsCommand {
+    /// byte offset of the edisk within the ROM
+    #[argp(positional)]
+    edisk_offset: u64,
+}
+
+/// Print a file's data fork from an edisk's HFS/MFS volume.
+#[derive(FromArgs)]
+#[argp(subcommand, name = "cat")]
+struct CatCommand {
+    /// byte offset of the edisk within the ROM
+    #[argp(positional)]
+    edisk_offset: u64,
+
+    /// path of the file to print, as shown by `ls`
+    #[argp(positional)]
+    path: String,
+
+    /// print the resource fork instead of the data fork
+    #[argp(switch)]
+    resource: bool,
+}
+
+/// Re-encode a `.dsk` image and write it back into an existing edisk's
+/// block table and data region in place, so a patched disk can be shipped
+/// inside the original ROM.
+#[derive(FromArgs)]
+#[argp(subcommand, name = "repack")]
+struct RepackCommand {
+    /// byte offset of the edisk within the ROM
+    #[argp(positional)]
+    edisk_offset: u64,
+
+    /// path to the (possibly modified) `.dsk` image to re-encode
+    #[argp(positional)]
+    dsk: PathBuf,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    let file = fs::File::open(&args.rom)?;
+    let mut rom = RomReader::new(file);
+    let codecs = CodecRegistry::new();
+
+    match &args.command {
+        Command::Ls(cmd) => return ls_edisk(&mut rom, &codecs, cmd.edisk_offset),
+        Command::Cat(cmd) => {
+            return cat_edisk(&mut rom, &codecs, cmd.edisk_offset, &cmd.path, cmd.resource)
+        }
+        Command::Repack(cmd) => return repack_edisk(&args.rom, cmd.edisk_offset, &cmd.dsk),
+        Command::List(_) | Command::Info(_) | Command::Extract(_) => {}
+    }
+
+    let len = rom.len()?;
+    let hash_db = args.verify.as_deref().map(HashDatabase::load).transpose()?;
+
+    let offsets: Box<dyn Iterator<Item = u64>> = match args.offset {
+        Some(offset) => Box::new(std::iter::once(offset)),
+        None => Box::new((0..len).step_by(EDISK_SCAN_STRIDE as usize)),
+    };
+
+    for location in offsets {
+        let header = match edisk::find_edisk(&mut rom, location) {
+            Ok(Some(header)) => header,
+            Ok(None) => continue,
+            Err(err) => {
+                report_skip(location, &err);
+                continue;
+            }
+        };
+        eprintln!("Found edisk at 0x{:06x}", header.location);
+
+        let result = match &args.command {
+            Command::List(_) => {
+                list_edisk(&header);
+                Ok(())
+            }
+            Command::Info(_) => info_edisk(&mut rom, &header, &codecs, hash_db.as_ref()),
+            Command::Extract(_) => {
+                extract_edisk(&mut rom, &header, &codecs, &args.out_dir, hash_db.as_ref())
+            }
+            Command::Ls(_) | Command::Cat(_) | Command::Repack(_) => {
+                unreachable!("handled before the scan loop")
+            }
+        };
+        if let Err(err) = result {
+            report_skip(header.location, &err);
+        }
+    }
+
+    Ok(())
+}
+
+fn list_edisk(header: &EdiskHeader) {
+    println!(
+        "0x{:06x}: version {}, block size {}, disk length {}",
+        header.location, header.version, header.block_size, header.disk_len
+    );
+}
+
+fn info_edisk<R: std::io::Read + std::io::Seek>(
+    rom: &mut RomReader<R>,
+    header: &EdiskHeader,
+    codecs: &CodecRegistry,
+    hash_db: Option<&HashDatabase>,
+) -> Result<()> {
+    println!("Edisk at 0x{:06x}", header.location);
+    println!("  Version:      {}", header.version);
+    println!("  Block size:   {}", header.block_size);
+    println!("  Disk length:  {}", header.disk_len);
+    println!("  Table offset: 0x{:06x}", header.table_offset);
+    println!("  Data offset:  0x{:06x}", header.data_offset);
+
+    let blocks = edisk::read_block_table(rom, header)?;
+    println!("  Block modes:");
+    for (mode, count) in edisk::block_mode_histogram(&blocks) {
+        println!("    mode {}: {} blocks", mode, count);
+    }
+
+    let (_, hashes) = edisk::extract_disk(rom, header, &blocks, codecs)?;
+    println!("  CRC32: {}", hashes.crc32_hex());
+    println!("  MD5:   {}", hashes.md5_hex());
+    println!("  SHA1:  {}", hashes.sha1_hex());
+    print_verify_result(hash_db, &hashes);
+
+    Ok(())
+}
+
+fn extract_edisk<R: std::io::Read + std::io::Seek>(
+    rom: &mut RomReader<R>,
+    header: &EdiskHeader,
+    codecs: &CodecRegistry,
+    out_dir: &std::path::Path,
+    hash_db: Option<&HashDatabase>,
+) -> Result<()> {
+    let blocks = edisk::read_block_table(rom, header)?;
+    let (disk, hashes) = edisk::extract_disk(rom, header, &blocks, codecs)?;
+
+    let path = out_dir.join(format!("EDisk-{:06x}.dsk", header.location));
+    eprintln!("Writing {}", path.display());
+    fs::write(path, disk)?;
+
+    println!("CRC32: {}", hashes.crc32_hex());
+    println!("MD5:   {}", hashes.md5_hex());
+    println!("SHA1:  {}", hashes.sha1_hex());
+    print_verify_result(hash_db, &hashes);
+
+    Ok(())
+}
+
+fn decode_edisk_at<R: std::io::Read + std::io::Seek>(
+    rom: &mut RomReader<R>,
+    codecs: &CodecRegistry,
+    edisk_offset: u64,
+) -> Result<Vec<u8>> {
+    let header = edisk::find_edisk(rom, edisk_offset)?
+        .with_context(|| format!("no edisk found at 0x{:06x}", edisk_offset))?;
+    let blocks = edisk::read_block_table(rom, &header)?;
+    let (disk, _) = edisk::extract_disk(rom, &header, &blocks, codecs)?;
+    Ok(disk)
+}
+
+fn ls_edisk<R: std::io::Read + std::io::Seek>(
+    rom: &mut RomReader<R>,
+    codecs: &CodecRegistry,
+    edisk_offset: u64,
+) -> Result<()> {
+    let disk = decode_edisk_at(rom, codecs, edisk_offset)?;
+    let volume = Volume::open(&disk)?;
+    for file in volume.list_files()? {
+        println!("{}", file.path);
+    }
+    Ok(())
+}
+
+fn cat_edisk<R: std::io::Read + std::io::Seek>(
+    rom: &mut RomReader<R>,
+    codecs: &CodecRegistry,
+    edisk_offset: u64,
+    path: &str,
+    resource: bool,
+) -> Result<()> {
+    let disk = decode_edisk_at(rom, codecs, edisk_offset)?;
+    let volume = Volume::open(&disk)?;
+    let data = if resource {
+        volume.read_resource_fork(path)?
+    } else {
+        volume.read_file(path)?
+    };
+    std::io::stdout().write_all(&data)?;
+    Ok(())
+}
+
+/// Re-encodes `dsk_path` and writes the result into the edisk at
+/// `edisk_offset`'s existing block table and data offsets, in place.
+///
+/// The encoder only knows 512-byte blocks, and the repacked data must fit
+/// in the space up to the next edisk's scan boundary, so both are checked
+/// up front rather than silently truncating or corrupting a neighbour.
+fn repack_edisk(rom_path: &std::path::Path, edisk_offset: u64, dsk_path: &std::path::Path) -> Result<()> {
+    let file = fs::OpenOptions::new().read(true).write(true).open(rom_path)?;
+    let mut rom = RomReader::new(file);
+
+    let header = edisk::find_edisk(&mut rom, edisk_offset)?
+        .with_context(|| format!("no edisk found at 0x{:06x}", edisk_offset))?;
+    if header.block_size != 512 {
+        bail!(
+            "repack only supports 512-byte blocks, but this edisk uses {}-byte blocks",
+            header.block_size
+        );
+    }
+
+    let disk = fs::read(dsk_path)?;
+    if disk.len() != header.disk_len {
+        bail!(
+            "{} is {} bytes, but the edisk at 0x{:06x} expects {} bytes",
+            dsk_path.display(),
+            disk.len(),
+            edisk_offset,
+            header.disk_len
+        );
+    }
+
+    let encoded = encoder::encode_disk(&disk)?;
+    let slot_len = EDISK_SCAN_STRIDE;
+    if header.table_offset + encoded.block_table.len() as u64 > slot_len {
+        bail!("re-encoded block table no longer fits before the next edisk's scan slot");
+    }
+    if header.data_offset + encoded.data.len() as u64 > slot_len {
+        bail!(
+            "re-encoded disk data ({} bytes) no longer fits before the next edisk's scan slot",
+            encoded.data.len()
+        );
+    }
+
+    rom.write_at(header.location + header.table_offset, &encoded.block_table)?;
+    rom.write_at(header.location + header.data_offset, &encoded.data)?;
+
+    println!(
+        "Repacked edisk at 0x{:06x}: {} bytes of block table, {} bytes of data",
+        header.location,
+        encoded.block_table.len(),
+        encoded.data.len()
+    );
+    Ok(())
+}
+
+/// Reports that the edisk at `location` couldn't be handled, so the scan
+/// loop can move on to the next one instead of aborting the whole run.
+fn report_skip(location: u64, err: impl std::fmt::Display) {
+    eprintln!("Skipping edisk at 0x{:06x}: {}", location, err);
+}
+
+fn print_verify_result(hash_db: Option<&HashDatabase>, hashes: &crate::hash::DiskHashes) {
+    let Some(hash_db) = hash_db else {
+        return;
+    };
+    match hash_db.verify(hashes) {
+        VerifyResult::Known(name) => println!("Verified: matches known disk \"{}\"", name),
+        VerifyResult::Unknown => println!("Verify: no matching entry in the hash database"),
+    }
+}
@@ -0,0 +1,317 @@
+//
+// Block codecs for edisk storage.
+//
+// Each edisk block is stored using one of a small number of encodings,
+// selected per-block by the mode in the top byte of the block table entry.
+// `BlockCodec` lets new modes be added without reworking `extract_block`'s
+// match statement, and `CodecRegistry` looks the right one up by mode.
+//
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+
+/// Upper bound on the number of storage bytes a codec may need to decode a
+/// single `block_size`-byte block. Every known codec's worst case is the
+/// block verbatim plus a bounded amount of framing (a repeat-op byte per
+/// run, or a 16-byte lookup table), so twice the block size is a
+/// comfortable bound. Used to size the read-ahead buffer in the caller.
+pub fn max_encoded_block_len(block_size: usize) -> usize {
+    block_size * 2
+}
+
+/// Decodes the storage bytes for one edisk block mode into an
+/// edisk-defined-size block.
+pub trait BlockCodec {
+    /// Decodes a block, filling `out` and returning the number of bytes of
+    /// `storage` consumed.
+    fn decode(&self, storage: &[u8], out: &mut [u8]) -> Result<usize>;
+}
+
+/// Mode 0: the block is stored byte-wise two's-complement negated.
+pub struct NegateCodec;
+
+impl BlockCodec for NegateCodec {
+    fn decode(&self, storage: &[u8], out: &mut [u8]) -> Result<usize> {
+        if storage.len() < out.len() {
+            bail!("negated block storage is shorter than the {}-byte block", out.len());
+        }
+        for (o, s) in out.iter_mut().zip(storage.iter()) {
+            *o = s.overflowing_neg().0;
+        }
+        Ok(out.len())
+    }
+}
+
+/// Mode 1: Macintosh "UnpackBits" RLE.
+pub struct UnpackBitsCodec;
+
+impl BlockCodec for UnpackBitsCodec {
+    fn decode(&self, storage: &[u8], out: &mut [u8]) -> Result<usize> {
+        let block_len = out.len();
+        let mut idx = 0;
+        let mut len = 0;
+        while len < block_len {
+            if idx >= storage.len() {
+                bail!("UnpackBits ran out of storage bytes before filling the {}-byte block", block_len);
+            }
+            let cmd = storage[idx];
+            idx += 1;
+            if cmd == 0x80 {
+                continue;
+            } else if cmd < 0x80 {
+                // Literal copy of cmd + 1 bytes.
+                let count = cmd as usize + 1;
+                if len + count > block_len {
+                    bail!("UnpackBits overran the {}-byte block boundary", block_len);
+                }
+                if idx + count > storage.len() {
+                    bail!("UnpackBits ran out of storage bytes before filling the {}-byte block", block_len);
+                }
+                out[len..len + count].copy_from_slice(&storage[idx..idx + count]);
+                idx += count;
+                len += count;
+            } else {
+                // n + 1 copies of next byte.
+                let n = cmd.overflowing_neg().0;
+                let count = n as usize + 1;
+                if len + count > block_len {
+                    bail!("UnpackBits overran the {}-byte block boundary", block_len);
+                }
+                if idx >= storage.len() {
+                    bail!("UnpackBits ran out of storage bytes before filling the {}-byte block", block_len);
+                }
+                let x = storage[idx];
+                idx += 1;
+                out[len..len + count].fill(x);
+                len += count;
+            }
+        }
+        if len != block_len {
+            bail!("UnpackBits overran the {}-byte block boundary", block_len);
+        }
+        Ok(idx)
+    }
+}
+
+/// Mode 2: a 16-entry most-common-byte lookup table plus a bitstream of
+/// table references and literal bytes.
+pub struct NibbleTableCodec;
+
+impl BlockCodec for NibbleTableCodec {
+    fn decode(&self, storage: &[u8], out: &mut [u8]) -> Result<usize> {
+        if storage.len() < 16 {
+            bail!("nibble table storage is shorter than the 16-byte lookup table");
+        }
+        let lookup = &storage[..16];
+        let mut stream = BitStream::from(&storage[16..]);
+        for o in out.iter_mut() {
+            *o = if stream.bit()? != 0 {
+                lookup[stream.bits(4)? as usize]
+            } else {
+                stream.bits(8)? as u8
+            };
+        }
+        Ok(16 + stream.byte_idx())
+    }
+}
+
+/// Looks up a `BlockCodec` by the mode stored in a block table entry's top
+/// byte.
+pub struct CodecRegistry {
+    codecs: HashMap<usize, Box<dyn BlockCodec>>,
+}
+
+impl CodecRegistry {
+    pub fn new() -> Self {
+        let mut codecs: HashMap<usize, Box<dyn BlockCodec>> = HashMap::new();
+        codecs.insert(0, Box::new(NegateCodec));
+        codecs.insert(1, Box::new(UnpackBitsCodec));
+        codecs.insert(2, Box::new(NibbleTableCodec));
+        CodecRegistry { codecs }
+    }
+
+    pub fn get(&self, mode: usize) -> Option<&dyn BlockCodec> {
+        self.codecs.get(&mode).map(|c| c.as_ref())
+    }
+}
+
+impl Default for CodecRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub(crate) struct BitStream<'a> {
+    bit_index: usize,
+    data: &'a [u8],
+}
+
+impl<'a> BitStream<'a> {
+    fn from(data: &'a [u8]) -> BitStream<'a> {
+        BitStream { bit_index: 0, data }
+    }
+
+    fn bit(&mut self) -> Result<u32> {
+        let byte_index = self.bit_index / 8;
+        if byte_index >= self.data.len() {
+            bail!("bitstream ran out of storage bytes");
+        }
+        let bit_num = self.bit_index % 8;
+
+        let byte = self.data[byte_index];
+        let bit = (byte >> (7 - bit_num)) & 1;
+
+        self.bit_index += 1;
+
+        Ok(bit as u32)
+    }
+
+    fn bits(&mut self, num_bits: u8) -> Result<u32> {
+        let mut res = 0;
+        for _ in 0..num_bits {
+            res = res << 1 | self.bit()?;
+        }
+        Ok(res)
+    }
+
+    fn byte_idx(&self) -> usize {
+        self.bit_index.div_ceil(8)
+    }
+}
+
+/// The write-side counterpart of `BitStream`, used by `encoder`'s nibble
+/// table codec to build its bitstream MSB-first, matching the order
+/// `BitStream` reads it back in.
+pub(crate) struct BitWriter {
+    bit_index: usize,
+    data: Vec<u8>,
+}
+
+impl BitWriter {
+    pub(crate) fn new() -> Self {
+        BitWriter {
+            bit_index: 0,
+            data: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push_bit(&mut self, bit: u32) {
+        let byte_index = self.bit_index / 8;
+        let bit_num = self.bit_index % 8;
+        if byte_index == self.data.len() {
+            self.data.push(0);
+        }
+        self.data[byte_index] |= ((bit & 1) as u8) << (7 - bit_num);
+        self.bit_index += 1;
+    }
+
+    pub(crate) fn push_bits(&mut self, value: u32, num_bits: u8) {
+        for i in (0..num_bits).rev() {
+            self.push_bit((value >> i) & 1);
+        }
+    }
+
+    pub(crate) fn finish(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negate_codec_decodes_byte_wise_twos_complement() {
+        let storage = [0x00, 0x01, 0xff, 0x80];
+        let mut out = [0u8; 4];
+        let consumed = NegateCodec.decode(&storage, &mut out).unwrap();
+        assert_eq!(consumed, 4);
+        assert_eq!(out, [0x00, 0xff, 0x01, 0x80]);
+    }
+
+    #[test]
+    fn unpack_bits_decodes_literal_run() {
+        // cmd 0x02 => literal copy of 3 bytes.
+        let storage = [0x02, b'a', b'b', b'c'];
+        let mut out = [0u8; 3];
+        let consumed = UnpackBitsCodec.decode(&storage, &mut out).unwrap();
+        assert_eq!(consumed, storage.len());
+        assert_eq!(&out, b"abc");
+    }
+
+    #[test]
+    fn unpack_bits_decodes_repeat_run() {
+        // cmd 0xfd => 4 copies of the next byte ((-(-3)) + 1 == 4).
+        let storage = [0xfd, 0x41];
+        let mut out = [0u8; 4];
+        let consumed = UnpackBitsCodec.decode(&storage, &mut out).unwrap();
+        assert_eq!(consumed, storage.len());
+        assert_eq!(out, [0x41; 4]);
+    }
+
+    #[test]
+    fn unpack_bits_errors_when_it_overruns_the_block_boundary() {
+        // A 4-byte literal run into a 3-byte block can't land exactly on
+        // the boundary, so this should error rather than silently
+        // truncating or reading past `out`.
+        let storage = [0x03, b'a', b'b', b'c', b'd'];
+        let mut out = [0u8; 3];
+        let err = UnpackBitsCodec.decode(&storage, &mut out).unwrap_err();
+        assert!(err.to_string().contains("overran"));
+    }
+
+    #[test]
+    fn unpack_bits_errors_when_storage_runs_out_mid_literal_run() {
+        // cmd 0x02 promises a 3-byte literal run, but only 2 bytes follow.
+        let storage = [0x02, b'a', b'b'];
+        let mut out = [0u8; 3];
+        let err = UnpackBitsCodec.decode(&storage, &mut out).unwrap_err();
+        assert!(err.to_string().contains("ran out of storage bytes"));
+    }
+
+    #[test]
+    fn unpack_bits_errors_when_storage_runs_out_mid_repeat_run() {
+        // cmd 0xfd (4 copies) with no byte to repeat.
+        let storage = [0xfd];
+        let mut out = [0u8; 4];
+        let err = UnpackBitsCodec.decode(&storage, &mut out).unwrap_err();
+        assert!(err.to_string().contains("ran out of storage bytes"));
+    }
+
+    #[test]
+    fn nibble_table_errors_when_storage_is_shorter_than_the_lookup_table() {
+        let storage = [0u8; 10];
+        let mut out = [0u8; 2];
+        let err = NibbleTableCodec.decode(&storage, &mut out).unwrap_err();
+        assert!(err.to_string().contains("16-byte lookup table"));
+    }
+
+    #[test]
+    fn nibble_table_errors_when_the_bitstream_runs_out() {
+        let storage = [0u8; 16]; // lookup table, but no bitstream bytes at all.
+        let mut out = [0u8; 1];
+        let err = NibbleTableCodec.decode(&storage, &mut out).unwrap_err();
+        assert!(err.to_string().contains("bitstream ran out"));
+    }
+
+    #[test]
+    fn nibble_table_decodes_table_references_and_literals() {
+        let mut lookup = [0u8; 16];
+        lookup[5] = 0x99;
+        let mut storage = lookup.to_vec();
+        let mut writer = BitWriter::new();
+        // Table reference to index 5.
+        writer.push_bit(1);
+        writer.push_bits(5, 4);
+        // Literal byte 0x42.
+        writer.push_bit(0);
+        writer.push_bits(0x42, 8);
+        storage.extend(writer.finish());
+
+        let mut out = [0u8; 2];
+        let consumed = NibbleTableCodec.decode(&storage, &mut out).unwrap();
+        assert_eq!(out, [0x99, 0x42]);
+        assert_eq!(consumed, storage.len());
+    }
+}
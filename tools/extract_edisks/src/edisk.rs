@@ -0,0 +1,188 @@
+//
+// Edisk header parsing and block table decoding.
+//
+// This is the library half of the extractor: scanning a ROM for edisks,
+// parsing their headers, and decoding their block tables into a flat disk
+// image. The CLI subcommands in `cli` drive this code and decide what to
+// do with the result (print a summary, or write it to disk).
+//
+
+use std::collections::BTreeMap;
+use std::io::{Read, Seek};
+
+use crate::bytes::{read_long, read_word};
+use crate::codec::{max_encoded_block_len, CodecRegistry};
+use crate::error::{EdiskError, Result};
+use crate::hash::{DiskHashes, DiskHasher};
+use crate::rom::RomReader;
+
+pub const EDISK_MAGIC: [u8; 12] = [
+    0x45, 0x44, 0x69, 0x73, 0x6B, 0x20, 0x47, 0x61, 0x72, 0x79, 0x20, 0x44,
+];
+
+/// Edisks are searched for on 64K boundaries.
+pub const EDISK_SCAN_STRIDE: u64 = 0x10000;
+
+/// The fields of an edisk's 512-byte header that the rest of the tool
+/// cares about.
+pub struct EdiskHeader {
+    pub location: u64,
+    pub block_size: u16,
+    pub version: u16,
+    pub disk_len: usize,
+    pub table_offset: u64,
+    pub data_offset: u64,
+}
+
+impl EdiskHeader {
+    pub fn num_blocks(&self) -> usize {
+        self.disk_len / self.block_size as usize
+    }
+}
+
+/// `block_size` is only meaningful if it's a power of two in this range:
+/// small enough that the 24-bit block table offsets stay useful, large
+/// enough to match the smallest size anyone's actually shipped an edisk
+/// with.
+const MIN_BLOCK_SIZE: u16 = 512;
+const MAX_BLOCK_SIZE: u16 = 4096;
+
+fn is_supported_block_size(block_size: u16) -> bool {
+    (MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&block_size) && block_size.is_power_of_two()
+}
+
+/// Reads the 512-byte header at `location` and returns it if it carries the
+/// edisk signature. Returns `None` (not an error) when `location` doesn't
+/// hold an edisk, so callers can keep scanning. Returns `Err` when it does
+/// hold an edisk but one in a version, block size, or shape this tool
+/// doesn't (yet) support, so callers can skip just that edisk instead of
+/// aborting the whole scan.
+pub fn find_edisk<R: Read + Seek>(
+    rom: &mut RomReader<R>,
+    location: u64,
+) -> Result<Option<EdiskHeader>> {
+    let mut header = [0u8; 512];
+    rom.read_at(location, &mut header)?;
+
+    // Check HdrSignature.
+    if header[132..][..12] != EDISK_MAGIC {
+        return Ok(None);
+    }
+
+    let block_size = read_word(&header, 128);
+    let version = read_word(&header, 130);
+
+    if version != 1 {
+        return Err(EdiskError::UnsupportedVersion(version));
+    }
+    if !is_supported_block_size(block_size) {
+        return Err(EdiskError::UnsupportedBlockSize(block_size));
+    }
+
+    let table_offset = read_long(&header, 156) as u64;
+    let data_offset = read_long(&header, 160) as u64;
+    let disk_len = read_long(&header, 144) as usize;
+
+    if !disk_len.is_multiple_of(block_size as usize) {
+        return Err(EdiskError::MisalignedDiskLength(disk_len as u32, block_size));
+    }
+
+    Ok(Some(EdiskHeader {
+        location,
+        block_size,
+        version,
+        disk_len,
+        table_offset,
+        data_offset,
+    }))
+}
+
+/// Reads the raw block table entries (mode in the top byte, 24-bit signed
+/// data offset in the rest) for an edisk.
+pub fn read_block_table<R: Read + Seek>(
+    rom: &mut RomReader<R>,
+    header: &EdiskHeader,
+) -> Result<Vec<usize>> {
+    let num_blocks = header.num_blocks();
+    let mut block_table = vec![0u8; num_blocks * 4];
+    rom.read_at(header.location + header.table_offset, &mut block_table)?;
+    Ok((0..num_blocks)
+        .map(|i| read_long(&block_table, i * 4) as usize)
+        .collect())
+}
+
+/// Counts how many blocks use each mode, for `info`'s summary.
+pub fn block_mode_histogram(blocks: &[usize]) -> BTreeMap<usize, usize> {
+    let mut histogram = BTreeMap::new();
+    for block in blocks {
+        *histogram.entry(block >> 24).or_insert(0) += 1;
+    }
+    histogram
+}
+
+/// Decodes every block of an edisk into a flat `disk_len`-byte image,
+/// alongside CRC32/MD5/SHA-1 hashes of the result so callers can verify
+/// the decode was byte-exact.
+pub fn extract_disk<R: Read + Seek>(
+    rom: &mut RomReader<R>,
+    header: &EdiskHeader,
+    blocks: &[usize],
+    codecs: &CodecRegistry,
+) -> Result<(Vec<u8>, DiskHashes)> {
+    let block_size = header.block_size as usize;
+    let mut disk = Vec::with_capacity(header.disk_len);
+    let mut hasher = DiskHasher::new();
+
+    for block in blocks.iter() {
+        let mode = block >> 24;
+        let mut offset = (block & 0x00ffffff) as i64;
+
+        // Yes, data can come before the start. Ugh.
+        if offset > 0x00800000 {
+            offset -= 0x01000000;
+        }
+
+        let mut out = vec![0u8; block_size];
+        extract_block(
+            rom,
+            header.location + header.data_offset,
+            mode,
+            offset,
+            codecs,
+            &mut out,
+        )?;
+        hasher.update(&out);
+        disk.extend_from_slice(&out);
+    }
+
+    Ok((disk, hasher.finalize()))
+}
+
+fn extract_block<R: Read + Seek>(
+    rom: &mut RomReader<R>,
+    data_base: u64,
+    mode: usize,
+    block_offset: i64,
+    codecs: &CodecRegistry,
+    out: &mut [u8],
+) -> Result<()> {
+    if mode == 0 && block_offset == 0 {
+        // Special case
+        out.fill(0);
+        return Ok(());
+    }
+
+    let storage_offset = data_base
+        .checked_add_signed(block_offset)
+        .ok_or(EdiskError::InvalidBlockOffset(block_offset, data_base))?;
+    let mut storage = vec![0u8; max_encoded_block_len(out.len())];
+    let avail = rom.read_at_partial(storage_offset, &mut storage)?;
+    storage.truncate(avail);
+
+    let codec = codecs
+        .get(mode)
+        .ok_or(EdiskError::UnsupportedBlockMode(mode))?;
+    codec.decode(&storage, out)?;
+
+    Ok(())
+}
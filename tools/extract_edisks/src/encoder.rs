@@ -0,0 +1,247 @@
+//
+// Edisk block encoder.
+//
+// The inverse of the `BlockCodec` decoders in `codec`: given a 512-byte
+// block, produce bytes for each of the three known modes and keep
+// whichever is shortest. This lets a `.dsk` be re-packed into an edisk
+// that re-extracts to the identical disk, so a patched ROM disk can be
+// written back into a ROM.
+
+use anyhow::{bail, Result};
+
+use crate::codec::{max_encoded_block_len, BitWriter};
+
+/// The modes `encode_smallest` knows how to produce. Kept in sync with the
+/// decoders registered in `CodecRegistry`.
+const KNOWN_MODES: [usize; 3] = [0, 1, 2];
+
+/// Encodes `block` under every known mode and returns the mode and bytes
+/// of the shortest encoding.
+pub fn encode_smallest(block: &[u8; 512]) -> (usize, Vec<u8>) {
+    KNOWN_MODES
+        .iter()
+        .map(|&mode| (mode, encode_as(mode, block)))
+        .min_by_key(|(_, data)| data.len())
+        .expect("KNOWN_MODES is non-empty")
+}
+
+fn encode_as(mode: usize, block: &[u8; 512]) -> Vec<u8> {
+    match mode {
+        0 => encode_negate(block),
+        1 => encode_unpack_bits(block),
+        2 => encode_nibble_table(block),
+        _ => unreachable!("no encoder for mode {}", mode),
+    }
+}
+
+/// Mode 0: byte-wise two's-complement negation.
+fn encode_negate(block: &[u8; 512]) -> Vec<u8> {
+    block.iter().map(|b| b.overflowing_neg().0).collect()
+}
+
+/// Mode 1: Macintosh "PackBits" RLE, the inverse of `UnpackBitsCodec`.
+///
+/// Runs of 3 or more repeated bytes become a repeat op (control byte
+/// `257 - n`, i.e. `(-(n - 1)) & 0xff`, followed by the byte); everything
+/// else is emitted as literal runs (`len - 1` followed by the bytes),
+/// neither kind ever crossing the 128-byte-per-op limit.
+fn encode_unpack_bits(block: &[u8; 512]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(max_encoded_block_len(512));
+    let mut literal_start = 0;
+    let mut i = 0;
+
+    while i < block.len() {
+        let byte = block[i];
+        let mut run = 1;
+        while i + run < block.len() && block[i + run] == byte {
+            run += 1;
+        }
+
+        if run >= 3 {
+            flush_literal(&mut out, &block[literal_start..i]);
+            emit_repeat_ops(&mut out, byte, run);
+            i += run;
+            literal_start = i;
+        } else {
+            i += 1;
+        }
+    }
+
+    flush_literal(&mut out, &block[literal_start..]);
+    out
+}
+
+fn flush_literal(out: &mut Vec<u8>, bytes: &[u8]) {
+    for chunk in bytes.chunks(128) {
+        out.push((chunk.len() - 1) as u8);
+        out.extend_from_slice(chunk);
+    }
+}
+
+fn emit_repeat_ops(out: &mut Vec<u8>, byte: u8, mut remaining: usize) {
+    while remaining > 0 {
+        let mut chunk = remaining.min(128);
+        // A repeat op can't encode a run of exactly 1, so avoid leaving a
+        // 1-byte remainder when splitting a run longer than 128.
+        if remaining - chunk == 1 {
+            chunk -= 1;
+        }
+        let cmd = 0u8.wrapping_sub((chunk - 1) as u8);
+        out.push(cmd);
+        out.push(byte);
+        remaining -= chunk;
+    }
+}
+
+/// Mode 2: a 16-entry lookup table of the most frequent byte values in the
+/// block, then a bitstream of table references and literal bytes, the
+/// inverse of `NibbleTableCodec`.
+fn encode_nibble_table(block: &[u8; 512]) -> Vec<u8> {
+    let mut counts = [0u32; 256];
+    for &b in block {
+        counts[b as usize] += 1;
+    }
+    let mut by_frequency: Vec<u8> = (0..=255).collect();
+    by_frequency.sort_by(|&a, &b| counts[b as usize].cmp(&counts[a as usize]).then(a.cmp(&b)));
+    let table: [u8; 16] = by_frequency[..16].try_into().unwrap();
+
+    let mut out = Vec::with_capacity(max_encoded_block_len(512));
+    out.extend_from_slice(&table);
+
+    let mut writer = BitWriter::new();
+    for &b in block {
+        match table.iter().position(|&t| t == b) {
+            Some(idx) => {
+                writer.push_bit(1);
+                writer.push_bits(idx as u32, 4);
+            }
+            None => {
+                writer.push_bit(0);
+                writer.push_bits(b as u32, 8);
+            }
+        }
+    }
+    out.extend_from_slice(&writer.finish());
+    out
+}
+
+/// A re-packed edisk's block table and backing data, ready to be written
+/// after an edisk header whose `table_offset`/`data_offset` point at them.
+pub struct EncodedDisk {
+    /// `num_blocks * 4` bytes: mode in the top byte, 24-bit signed data
+    /// offset (relative to the header's `data_offset`) in the rest.
+    pub block_table: Vec<u8>,
+    /// The encoded blocks, packed back-to-back in block order.
+    pub data: Vec<u8>,
+}
+
+/// Encodes a flat, decoded disk image (as produced by `edisk::extract_disk`)
+/// back into edisk form. Errors (rather than panics) if the re-encoded data
+/// would need an offset beyond the largest positive 24-bit signed offset a
+/// block table entry can hold, so a caller can report that and skip writing a corrupt repack.
+pub fn encode_disk(disk: &[u8]) -> Result<EncodedDisk> {
+    assert_eq!(disk.len() % 512, 0, "disk image must be a whole number of blocks");
+
+    let mut block_table = Vec::with_capacity(disk.len() / 512 * 4);
+    let mut data = Vec::new();
+
+    for chunk in disk.chunks(512) {
+        let block: [u8; 512] = chunk.try_into().unwrap();
+
+        if block == [0u8; 512] {
+            // Special case mirrored from `extract_block`: an all-zero
+            // block is mode 0 with a zero offset, and stores no bytes.
+            block_table.extend_from_slice(&[0, 0, 0, 0]);
+            continue;
+        }
+
+        let (mode, encoded) = encode_smallest(&block);
+        // Mode 0 at offset 0 is the all-zero special case, so a stored
+        // mode 0 block must not start there.
+        if mode == 0 && data.is_empty() {
+            data.push(0);
+        }
+        let offset = data.len() as u32;
+        // `extract_disk` reads offsets above 0x800000 as negative.
+        if offset > 0x0080_0000 {
+            bail!("encoded disk exceeds the 24-bit signed block table offset range");
+        }
+
+        data.extend_from_slice(&encoded);
+        let entry = ((mode as u32) << 24) | offset;
+        block_table.extend_from_slice(&entry.to_be_bytes());
+    }
+
+    Ok(EncodedDisk { block_table, data })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::CodecRegistry;
+
+    fn assert_round_trips(block: [u8; 512]) {
+        let codecs = CodecRegistry::new();
+        let (mode, encoded) = encode_smallest(&block);
+
+        let mut decoded = [0u8; 512];
+        codecs
+            .get(mode)
+            .unwrap_or_else(|| panic!("no decoder registered for mode {}", mode))
+            .decode(&encoded, &mut decoded)
+            .expect("decode should succeed on our own encoding");
+
+        assert_eq!(decoded, block, "encode(mode {})/decode round trip", mode);
+    }
+
+    #[test]
+    fn round_trips_all_zero_block() {
+        assert_round_trips([0u8; 512]);
+    }
+
+    #[test]
+    fn round_trips_long_repeated_run() {
+        // Long enough to force the repeat op to split across multiple
+        // 128-byte chunks.
+        assert_round_trips([0xaa; 512]);
+    }
+
+    #[test]
+    fn round_trips_run_of_odd_length() {
+        let mut block = [0x41; 512];
+        // A 129-byte run exercises the "don't leave a 1-byte remainder"
+        // split in `emit_repeat_ops`.
+        for b in block.iter_mut().skip(129) {
+            *b = 0x42;
+        }
+        assert_round_trips(block);
+    }
+
+    #[test]
+    fn round_trips_block_favouring_the_nibble_table() {
+        // A handful of distinct bytes, none repeated long enough to beat
+        // the nibble table's per-byte cost.
+        let mut block = [0u8; 512];
+        for (i, b) in block.iter_mut().enumerate() {
+            *b = (i % 7) as u8;
+        }
+        assert_round_trips(block);
+    }
+
+    #[test]
+    fn round_trips_high_entropy_block() {
+        // No byte value dominates and there are no useful runs, so neither
+        // UnpackBits nor the nibble table beats mode 0 negation, which
+        // stores the block at its own size.
+        let mut block = [0u8; 512];
+        let mut x: u32 = 0x2545f4914f6cdd1d_u64 as u32;
+        for b in block.iter_mut() {
+            // A small xorshift PRNG -- deterministic without `rand`.
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            *b = (x & 0xff) as u8;
+        }
+        assert_round_trips(block);
+    }
+}
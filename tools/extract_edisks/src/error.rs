@@ -0,0 +1,36 @@
+//
+// Error types for edisk parsing.
+//
+// Header-level problems (an unsupported version, block size, or block
+// mode) are typed so callers can tell them apart from "this location
+// isn't an edisk at all" and decide to skip just that edisk and keep
+// scanning, rather than aborting the whole run.
+//
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum EdiskError {
+    #[error("unsupported edisk version {0} (only version 1 is supported)")]
+    UnsupportedVersion(u16),
+
+    #[error("unsupported block size {0} (must be a power of two from 512 to 4096)")]
+    UnsupportedBlockSize(u16),
+
+    #[error("disk length {0} is not a whole number of {1}-byte blocks")]
+    MisalignedDiskLength(u32, u16),
+
+    #[error("unsupported block mode {0}")]
+    UnsupportedBlockMode(usize),
+
+    #[error("block table offset {0} is out of range for data base 0x{1:06x}")]
+    InvalidBlockOffset(i64, u64),
+
+    #[error("failed to decode block: {0}")]
+    Decode(#[from] anyhow::Error),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, EdiskError>;
@@ -0,0 +1,74 @@
+//
+// Integrity hashing of decoded disk images.
+//
+// Edisk block modes are lossy-looking (negation, RLE, a nibble table), so
+// the only way to be confident a decode produced a byte-exact copy of the
+// original disk is to hash it and compare against a known-good value —
+// the same approach nodtool uses for its extracted images.
+//
+
+use md5::{Digest, Md5};
+use sha1::Sha1;
+
+/// Running CRC32/MD5/SHA-1 state over a disk image as it's assembled block
+/// by block.
+pub struct DiskHasher {
+    crc32: crc32fast::Hasher,
+    md5: Md5,
+    sha1: Sha1,
+}
+
+impl DiskHasher {
+    pub fn new() -> Self {
+        DiskHasher {
+            crc32: crc32fast::Hasher::new(),
+            md5: Md5::new(),
+            sha1: Sha1::new(),
+        }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.crc32.update(bytes);
+        self.md5.update(bytes);
+        self.sha1.update(bytes);
+    }
+
+    pub fn finalize(self) -> DiskHashes {
+        DiskHashes {
+            crc32: self.crc32.finalize(),
+            md5: self.md5.finalize().into(),
+            sha1: self.sha1.finalize().into(),
+        }
+    }
+}
+
+impl Default for DiskHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// CRC32/MD5/SHA-1 of a decoded disk image.
+pub struct DiskHashes {
+    pub crc32: u32,
+    pub md5: [u8; 16],
+    pub sha1: [u8; 20],
+}
+
+impl DiskHashes {
+    pub fn crc32_hex(&self) -> String {
+        format!("{:08x}", self.crc32)
+    }
+
+    pub fn md5_hex(&self) -> String {
+        hex(&self.md5)
+    }
+
+    pub fn sha1_hex(&self) -> String {
+        hex(&self.sha1)
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,56 @@
+//
+// Streaming access to a ROM image.
+//
+// Previously the extractor worked on a `&[u8]` produced by `fs::read`ing
+// the whole ROM into memory. `RomReader` instead wraps anything that's
+// `Read + Seek`, so the tool can work on ROMs too large to hold in memory,
+// or on a memory-mapped file, without the call sites caring which.
+//
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+pub struct RomReader<R> {
+    inner: R,
+}
+
+impl<R: Write + Seek> RomReader<R> {
+    /// Writes `buf` starting at `offset`, for patching a repacked edisk's
+    /// block table and data back into the underlying ROM.
+    pub fn write_at(&mut self, offset: u64, buf: &[u8]) -> io::Result<()> {
+        self.inner.seek(SeekFrom::Start(offset))?;
+        self.inner.write_all(buf)
+    }
+}
+
+impl<R: Read + Seek> RomReader<R> {
+    pub fn new(inner: R) -> Self {
+        RomReader { inner }
+    }
+
+    /// Reads exactly `buf.len()` bytes starting at `offset`.
+    pub fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        self.inner.seek(SeekFrom::Start(offset))?;
+        self.inner.read_exact(buf)?;
+        Ok(())
+    }
+
+    /// Reads up to `buf.len()` bytes starting at `offset`, returning how
+    /// many were actually available. Used for read-ahead windows that may
+    /// run past the end of the ROM.
+    pub fn read_at_partial(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.seek(SeekFrom::Start(offset))?;
+        let mut read = 0;
+        while read < buf.len() {
+            match self.inner.read(&mut buf[read..])? {
+                0 => break,
+                n => read += n,
+            }
+        }
+        Ok(read)
+    }
+
+    /// Total length of the underlying ROM image.
+    pub fn len(&mut self) -> io::Result<u64> {
+        self.inner.seek(SeekFrom::End(0))
+    }
+}
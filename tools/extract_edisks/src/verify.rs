@@ -0,0 +1,50 @@
+//
+// Redump-style verification against a known-good hash datfile.
+//
+// The datfile is a plain text list of `<sha1-hex>  <disk name>` lines, one
+// per known-good disk. It's intentionally simple rather than redump's full
+// XML dat format, since we only need a hash -> name lookup.
+//
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::hash::DiskHashes;
+
+pub struct HashDatabase {
+    by_sha1: HashMap<String, String>,
+}
+
+impl HashDatabase {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let mut by_sha1 = HashMap::new();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some((sha1, name)) = line.split_once(char::is_whitespace) {
+                by_sha1.insert(sha1.trim().to_lowercase(), name.trim().to_string());
+            }
+        }
+        Ok(HashDatabase { by_sha1 })
+    }
+
+    /// Looks up a disk's known name by its SHA-1, and reports whether it
+    /// was found in the database.
+    pub fn verify(&self, hashes: &DiskHashes) -> VerifyResult {
+        match self.by_sha1.get(&hashes.sha1_hex()) {
+            Some(name) => VerifyResult::Known(name.clone()),
+            None => VerifyResult::Unknown,
+        }
+    }
+}
+
+pub enum VerifyResult {
+    Known(String),
+    Unknown,
+}